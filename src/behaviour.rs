@@ -0,0 +1,9 @@
+use libp2p::{gossipsub, kad, kad::store::MemoryStore as TStore, mdns, swarm::NetworkBehaviour};
+
+// Create a custom behaviour for the network
+#[derive(NetworkBehaviour)]
+pub struct MyBehaviour {
+    pub gossipsub: gossipsub::Behaviour,
+    pub mdns: mdns::tokio::Behaviour,
+    pub kademlia: kad::Behaviour<TStore>,
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+/// Gossipsub topic names the `Echo`/`Ready` batches are published on,
+/// separate from the `transaction` topic carrying the actual payloads.
+pub const ECHO_TOPIC: &str = "chain-gossip-echo";
+pub const READY_TOPIC: &str = "chain-gossip-ready";
+
+/// The gossipsub `MessageId` a transaction was first seen under, carried as
+/// the bytes of its string form so it can live in a `Batch` without pulling
+/// gossipsub types into the wire format.
+pub type TransactionId = Vec<u8>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Kind {
+    Echo,
+    Ready,
+}
+
+/// A coalesced set of echo/ready attestations, flushed together to cut
+/// message volume instead of gossiping one message per attestation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Batch {
+    pub entries: Vec<(TransactionId, Kind)>,
+}
+
+/// Accumulates outgoing echo/ready attestations and hands back a [`Batch`]
+/// once it's full; the caller is still responsible for flushing on a timer
+/// for batches that never fill up.
+#[derive(Debug)]
+pub struct BatchBuffer {
+    pending: Vec<(TransactionId, Kind)>,
+    flush_at: usize,
+}
+
+impl BatchBuffer {
+    pub fn new(flush_at: usize) -> Self {
+        Self {
+            pending: Vec::new(),
+            flush_at,
+        }
+    }
+
+    /// Queues an attestation, returning a full batch if this push reached
+    /// the flush threshold.
+    pub fn push(&mut self, id: TransactionId, kind: Kind) -> Option<Batch> {
+        self.pending.push((id, kind));
+        if self.pending.len() >= self.flush_at {
+            Some(self.take())
+        } else {
+            None
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Drains whatever is pending into a batch, e.g. on a flush timer.
+    pub fn take(&mut self) -> Batch {
+        Batch {
+            entries: std::mem::take(&mut self.pending),
+        }
+    }
+}
+
+struct TransactionState {
+    echoes: HashSet<PeerId>,
+    readys: HashSet<PeerId>,
+    echoed: bool,
+    readied: bool,
+    delivered: bool,
+    /// When this id's state was first created, so [`ReliableBroadcast::prune`]
+    /// can age out ids whose protocol run stalled (e.g. the payload or enough
+    /// attestations never showed up) instead of keeping them forever.
+    first_seen: Instant,
+}
+
+impl Default for TransactionState {
+    fn default() -> Self {
+        Self {
+            echoes: HashSet::new(),
+            readys: HashSet::new(),
+            echoed: false,
+            readied: false,
+            delivered: false,
+            first_seen: Instant::now(),
+        }
+    }
+}
+
+/// Implements double-echo reliable broadcast on top of whatever transport
+/// carries the `Echo`/`Ready` attestations (gossipsub topics, here). Per
+/// transaction `h`: echo once on first sight, ready once echoes cross `E`,
+/// amplify by readying (if not already readied) once readys cross `F + 1`,
+/// and deliver once
+/// readys cross `R`.
+pub struct ReliableBroadcast {
+    echo_threshold: usize,
+    ready_amplification_threshold: usize,
+    delivery_threshold: usize,
+    /// How long an id's state is kept around without being delivered before
+    /// [`Self::prune`] evicts it, bounding memory from transactions whose
+    /// protocol run stalls.
+    state_ttl: Duration,
+    /// Hard cap on tracked ids, enforced by [`Self::prune`] by evicting the
+    /// oldest entries first, on top of `state_ttl`.
+    max_tracked: usize,
+    state: HashMap<TransactionId, TransactionState>,
+}
+
+impl ReliableBroadcast {
+    pub fn new(
+        echo_threshold: usize,
+        ready_amplification_threshold: usize,
+        delivery_threshold: usize,
+        state_ttl: Duration,
+        max_tracked: usize,
+    ) -> Self {
+        Self {
+            echo_threshold,
+            ready_amplification_threshold,
+            delivery_threshold,
+            state_ttl,
+            max_tracked,
+            state: HashMap::new(),
+        }
+    }
+
+    /// Called the first time we see `id`'s transaction payload. Returns
+    /// `true` exactly once per `id`, when we should broadcast `Echo(id)`.
+    pub fn on_transaction_seen(&mut self, id: TransactionId) -> bool {
+        let state = self.state.entry(id).or_default();
+        if state.echoed {
+            return false;
+        }
+        state.echoed = true;
+        true
+    }
+
+    /// Records an `Echo(id)` from `from`. Ignores a peer's duplicate echo.
+    /// Returns `true` the first time distinct echoes for `id` cross the
+    /// echo threshold, meaning we should broadcast `Ready(id)`.
+    pub fn on_echo(&mut self, id: TransactionId, from: PeerId) -> bool {
+        let state = self.state.entry(id).or_default();
+        if !state.echoes.insert(from) {
+            return false;
+        }
+        if !state.readied && state.echoes.len() >= self.echo_threshold {
+            state.readied = true;
+            return true;
+        }
+        false
+    }
+
+    /// Records a `Ready(id)` from `from`. Ignores a peer's duplicate ready.
+    /// Returns `(should_amplify, should_deliver)`: whether we should
+    /// broadcast `Ready(id)` ourselves (amplification, crossing `F + 1`
+    /// readys even without our own echo threshold met) and whether `id` has
+    /// now crossed the delivery threshold `R` for the first time.
+    pub fn on_ready(&mut self, id: TransactionId, from: PeerId) -> (bool, bool) {
+        let state = self.state.entry(id).or_default();
+        if !state.readys.insert(from) {
+            return (false, false);
+        }
+
+        let should_amplify =
+            !state.readied && state.readys.len() >= self.ready_amplification_threshold;
+        if should_amplify {
+            state.readied = true;
+        }
+
+        let should_deliver = !state.delivered && state.readys.len() >= self.delivery_threshold;
+        if should_deliver {
+            state.delivered = true;
+        }
+
+        (should_amplify, should_deliver)
+    }
+
+    /// Drops `id`'s tracking state once its transaction has been delivered,
+    /// instead of keeping a `delivered: true` entry around forever.
+    pub fn forget(&mut self, id: &TransactionId) {
+        self.state.remove(id);
+    }
+
+    /// Bounds `state`'s memory: evicts ids older than `state_ttl`, then, if
+    /// still over `max_tracked`, evicts the oldest remaining ids until it
+    /// isn't. Meant to be called periodically rather than on every update.
+    pub fn prune(&mut self) {
+        let now = Instant::now();
+        self.state
+            .retain(|_, state| now.duration_since(state.first_seen) < self.state_ttl);
+
+        if self.state.len() > self.max_tracked {
+            let mut by_age: Vec<_> = self
+                .state
+                .iter()
+                .map(|(id, state)| (id.clone(), state.first_seen))
+                .collect();
+            by_age.sort_by_key(|(_, first_seen)| *first_seen);
+            let excess = self.state.len() - self.max_tracked;
+            for (id, _) in by_age.into_iter().take(excess) {
+                self.state.remove(&id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn broadcast(
+        echo_threshold: usize,
+        ready_amplification_threshold: usize,
+        delivery_threshold: usize,
+    ) -> ReliableBroadcast {
+        ReliableBroadcast::new(
+            echo_threshold,
+            ready_amplification_threshold,
+            delivery_threshold,
+            Duration::from_secs(600),
+            4096,
+        )
+    }
+
+    #[test]
+    fn batch_buffer_flushes_at_threshold() {
+        let mut buffer = BatchBuffer::new(2);
+        assert!(buffer.push(b"a".to_vec(), Kind::Echo).is_none());
+        assert!(!buffer.is_empty());
+        let batch = buffer
+            .push(b"b".to_vec(), Kind::Echo)
+            .expect("threshold reached");
+        assert_eq!(batch.entries.len(), 2);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn batch_buffer_take_drains_without_reaching_threshold() {
+        let mut buffer = BatchBuffer::new(100);
+        buffer.push(b"a".to_vec(), Kind::Echo);
+        let batch = buffer.take();
+        assert_eq!(batch.entries.len(), 1);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn duplicate_echo_from_same_peer_is_ignored() {
+        let mut b = broadcast(2, 2, 3);
+        let id = b"tx".to_vec();
+        let peer = PeerId::random();
+        assert!(!b.on_echo(id.clone(), peer));
+        assert!(!b.on_echo(id, peer));
+    }
+
+    #[test]
+    fn echo_threshold_crossed_exactly_once() {
+        let mut b = broadcast(2, 2, 3);
+        let id = b"tx".to_vec();
+        assert!(!b.on_echo(id.clone(), PeerId::random()));
+        assert!(b.on_echo(id.clone(), PeerId::random()));
+        // A third distinct echo has already crossed the threshold; it must
+        // not report crossing it again.
+        assert!(!b.on_echo(id, PeerId::random()));
+    }
+
+    #[test]
+    fn ready_amplification_and_delivery_thresholds_are_distinct() {
+        let mut b = broadcast(2, 2, 3);
+        let id = b"tx".to_vec();
+
+        assert_eq!(b.on_ready(id.clone(), PeerId::random()), (false, false));
+        // Second distinct ready crosses the amplification threshold (F + 1 =
+        // 2) but not yet the higher delivery threshold (R = 3).
+        assert_eq!(b.on_ready(id.clone(), PeerId::random()), (true, false));
+        // Third distinct ready crosses delivery, but amplification already
+        // fired so it must not fire again.
+        assert_eq!(b.on_ready(id, PeerId::random()), (false, true));
+    }
+
+    #[test]
+    fn duplicate_ready_from_same_peer_is_ignored() {
+        let mut b = broadcast(2, 2, 3);
+        let id = b"tx".to_vec();
+        let peer = PeerId::random();
+        b.on_ready(id.clone(), peer);
+        assert_eq!(b.on_ready(id, peer), (false, false));
+    }
+
+    #[test]
+    fn forget_removes_delivered_state() {
+        let mut b = broadcast(2, 2, 1);
+        let id = b"tx".to_vec();
+        b.on_ready(id.clone(), PeerId::random());
+        assert_eq!(b.state.len(), 1);
+        b.forget(&id);
+        assert!(b.state.is_empty());
+    }
+
+    #[test]
+    fn prune_evicts_entries_older_than_ttl() {
+        let mut b = ReliableBroadcast::new(2, 2, 3, Duration::from_millis(1), 4096);
+        b.on_echo(b"tx".to_vec(), PeerId::random());
+        std::thread::sleep(Duration::from_millis(10));
+        b.prune();
+        assert!(b.state.is_empty());
+    }
+
+    #[test]
+    fn prune_evicts_oldest_past_max_tracked() {
+        let mut b = ReliableBroadcast::new(2, 2, 3, Duration::from_secs(600), 1);
+        b.on_echo(b"older".to_vec(), PeerId::random());
+        std::thread::sleep(Duration::from_millis(5));
+        b.on_echo(b"newer".to_vec(), PeerId::random());
+        b.prune();
+        assert_eq!(b.state.len(), 1);
+        assert!(b.state.contains_key(b"newer".as_slice()));
+    }
+}
@@ -0,0 +1,75 @@
+use libp2p::Multiaddr;
+
+/// Runtime configuration for a [`crate::node::NodeRunner`], gathered from CLI
+/// args/env by the binary and threaded through to the library.
+#[derive(Debug, Clone)]
+pub struct NodeConfig {
+    /// Multiaddrs (optionally carrying a `/p2p/<peer-id>` suffix) of public
+    /// bootstrap nodes to dial on startup, so the node can join a wider swarm
+    /// than what mDNS discovers on the local network.
+    pub bootstrap_peers: Vec<Multiaddr>,
+    /// Listen on `/ip4/0.0.0.0/udp/0/quic-v1`.
+    pub enable_quic: bool,
+    /// Listen on `/ip4/0.0.0.0/tcp/0`.
+    pub enable_tcp: bool,
+    /// Listen on `/ip4/0.0.0.0/tcp/0/ws`, for peers only reachable over
+    /// WebSocket (e.g. browsers).
+    pub enable_websocket: bool,
+    /// Double-echo thresholds: broadcast `Ready` once this many distinct
+    /// peers have echoed a transaction.
+    pub echo_threshold: usize,
+    /// Broadcast `Ready` (amplification) once this many distinct peers have
+    /// readied a transaction, even without our own echo threshold met.
+    /// Conventionally `F + 1` for a tolerated `F` Byzantine peers.
+    pub ready_amplification_threshold: usize,
+    /// Deliver a transaction once this many distinct peers have readied it.
+    pub delivery_threshold: usize,
+    /// Flush a node's pending echo/ready batch once it holds this many
+    /// entries, without waiting for the flush timer.
+    pub batch_max_entries: usize,
+    /// Flush a node's pending echo/ready batch on this interval even if it
+    /// hasn't reached `batch_max_entries`.
+    pub batch_flush_interval: std::time::Duration,
+    /// How long a `put_record`ed transaction/peer record stays alive before
+    /// it's evicted, unless it's republished first.
+    pub record_ttl: std::time::Duration,
+    /// How often a still-valid record we're the closest holder of gets
+    /// republished, so it survives its original publisher going offline.
+    pub record_republish_interval: std::time::Duration,
+    /// Caps how many records this node's `MemoryStore` will hold, so a flood
+    /// of gossiped transactions can't memory-exhaust it.
+    pub max_records: usize,
+    /// Caps the size in bytes of any single record this node will store.
+    pub max_record_size_bytes: usize,
+    /// How long a transaction's echo/ready tracking state (in
+    /// [`crate::broadcast::ReliableBroadcast`] and the pending/ready-for-
+    /// delivery sets) is kept around without being delivered before it's
+    /// pruned, bounding memory from stalled broadcasts.
+    pub broadcast_state_ttl: std::time::Duration,
+    /// Hard cap on tracked ids in [`crate::broadcast::ReliableBroadcast`],
+    /// enforced alongside `broadcast_state_ttl` by evicting the oldest
+    /// entries first.
+    pub max_tracked_transactions: usize,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            bootstrap_peers: Vec::new(),
+            enable_quic: true,
+            enable_tcp: true,
+            enable_websocket: false,
+            echo_threshold: 2,
+            ready_amplification_threshold: 2,
+            delivery_threshold: 3,
+            batch_max_entries: 16,
+            batch_flush_interval: std::time::Duration::from_millis(100),
+            record_ttl: std::time::Duration::from_secs(36 * 60 * 60),
+            record_republish_interval: std::time::Duration::from_secs(22 * 60 * 60),
+            max_records: 1024,
+            max_record_size_bytes: 64 * 1024,
+            broadcast_state_ttl: std::time::Duration::from_secs(10 * 60),
+            max_tracked_transactions: 4096,
+        }
+    }
+}
@@ -0,0 +1,9 @@
+pub mod behaviour;
+pub mod broadcast;
+pub mod config;
+pub mod node;
+pub mod transaction;
+
+pub use config::NodeConfig;
+pub use node::{new_node, Command, Event, Node, NodeRunner};
+pub use transaction::TransactionMessage;
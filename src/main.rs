@@ -1,196 +1,100 @@
-use libp2p::{
-    futures::StreamExt,
-    gossipsub,
-    mdns,
-    kad::{self, store::MemoryStore as TStore, Record, RecordKey, Quorum},
-    swarm::{SwarmEvent, NetworkBehaviour},
-    Multiaddr,
-};
-use std::{
-    collections::hash_map::DefaultHasher,
-    error::Error,
-    hash::{Hash, Hasher},
-    time::{SystemTime, Duration},
-};
-use tokio::{io, io::AsyncBufReadExt, select};
-use serde::{Serialize, Deserialize};
-
-// Create a custom behaviour for the network
-#[derive(NetworkBehaviour)]
-struct MyBehaviour {
-    gossipsub: gossipsub::Behaviour,
-    mdns: mdns::tokio::Behaviour,
-    kademlia: kad::Behaviour<TStore>,
-}
-
-// Struct to handle transaction messages
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct TransactionMessage {
-    signature: String,
-    sender: String,
-    timestamp: u64,
-    tx_data: Vec<u8>,
+use std::{env, error::Error};
+
+use chain_gossip::{new_node, Event, NodeConfig};
+use tokio::io::{self, AsyncBufReadExt};
+
+/// Collects bootstrap multiaddrs from repeated `--bootstrap <multiaddr>` CLI
+/// flags, falling back to a comma-separated `CHAIN_GOSSIP_BOOTSTRAP_PEERS`
+/// env var when no flags are given.
+fn bootstrap_peers_from_env() -> Vec<libp2p::Multiaddr> {
+    let mut args = env::args().skip(1);
+    let mut peers = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--bootstrap" {
+            if let Some(addr) = args.next() {
+                match addr.parse() {
+                    Ok(addr) => peers.push(addr),
+                    Err(e) => eprintln!("Ignoring invalid --bootstrap address {addr}: {e}"),
+                }
+            }
+        }
+    }
+    if peers.is_empty() {
+        if let Ok(env_peers) = env::var("CHAIN_GOSSIP_BOOTSTRAP_PEERS") {
+            for addr in env_peers
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+            {
+                match addr.parse() {
+                    Ok(addr) => peers.push(addr),
+                    Err(e) => eprintln!("Ignoring invalid bootstrap address {addr}: {e}"),
+                }
+            }
+        }
+    }
+    peers
 }
 
-fn create_dummy_transaction(sender: String) -> TransactionMessage {
-    // Function to create dummy transaction
-    TransactionMessage {
-        signature: format!("signature-{}", rand::random::<u64>()),
-        sender,
-        timestamp: SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap()
-            .as_secs(),
-        tx_data: vec![0, 1, 2, 3],
+/// Reads `--disable-quic`/`--disable-tcp`/`--enable-websocket` flags on top
+/// of the [`NodeConfig`] transport defaults.
+fn transport_flags_from_env(config: &mut NodeConfig) {
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--disable-quic" => config.enable_quic = false,
+            "--disable-tcp" => config.enable_tcp = false,
+            "--enable-websocket" => config.enable_websocket = true,
+            _ => {}
+        }
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let mut swarm = libp2p::SwarmBuilder::with_new_identity()
-        .with_tokio()
-        .with_quic()
-        .with_behaviour(|key| {
-            // To content-address message, we can take the hash of message and use it as an ID.
-            let message_id_fn = |message: &libp2p::gossipsub::Message| {
-                let mut s = DefaultHasher::new();
-                message.data.hash(&mut s);
-                libp2p::gossipsub::MessageId::from(s.finish().to_string())
-            };
-
-            // Create a gossipsub config
-            let gossipsub_config = gossipsub::ConfigBuilder::default()
-                .heartbeat_interval(Duration::from_secs(5))
-                .validation_mode(gossipsub::ValidationMode::Strict)
-                .message_id_fn(message_id_fn)
-                .build()
-                .map_err(|msg| io::Error::new(io::ErrorKind::Other, msg))?;
-
-            // Build a gossipsub network behaviour
-            let gossipsub = gossipsub::Behaviour::new(
-                gossipsub::MessageAuthenticity::Signed(key.clone()),
-                gossipsub_config,
-            )?;
-
-            // Build a kademlia network behaviour
-            let kademlia = kad::Behaviour::new(
-                key.public().to_peer_id(),
-                TStore::new(key.public().to_peer_id())
-            );
-
-            // Build a mdns network behaviour
-            let mdns = mdns::tokio::Behaviour::new(
-                mdns::Config::default(),
-                key.public().to_peer_id()
-            )?;
-
-            Ok(MyBehaviour { gossipsub, mdns, kademlia })
-        })?
-        .build();
-
-    // Subscribe to a topic
-    let topic = gossipsub::IdentTopic::new("transaction");
-    swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
-
-    // Listen on a local address using QUIC (UDP)
-    let addr: Multiaddr = "/ip4/0.0.0.0/udp/0/quic-v1".parse().unwrap();
-    swarm.listen_on(addr)?;
+    let mut config = NodeConfig {
+        bootstrap_peers: bootstrap_peers_from_env(),
+        ..Default::default()
+    };
+    transport_flags_from_env(&mut config);
+    let (node, runner, mut events) = new_node(config).await?;
+    tokio::spawn(runner.run());
 
     println!("Node is listening for connections...");
 
-    // Read full lines from stdin
-    let mut stdin = io::BufReader::new(io::stdin()).lines();
-
-    // Get the local peer ID
-    let mut local_peer_id = swarm.local_peer_id().to_string();
-
-    // Handle swarm events
-    loop {
-        select! {
-            Ok(Some(line)) = stdin.next_line() => {
-                // Create a dummy transaction
-                let transaction = create_dummy_transaction(local_peer_id.clone());
-
-                // Serialize the transaction
-                let serialized_transaction = bincode::serialize(&transaction).unwrap();
-
-                // Publish the serialized transaction to the GossipSub network
-                if let Err(e) = swarm
-                    .behaviour_mut().gossipsub
-                    .publish(topic.clone(), serialized_transaction) {
-                    println!("Publish error: {e:?}");
-                } else {
-                    println!("Published transaction: {:?}", transaction);
+    // Print events emitted by the runner as they arrive.
+    let node_for_events = node.clone();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            match event {
+                Event::TransactionReceived {
+                    transaction,
+                    source,
+                } => {
+                    println!("Received transaction: {transaction:?} from peer: {source}");
+                }
+                Event::PeerDiscovered(peer_id) => {
+                    println!("mDNS discovered a new peer: {peer_id}");
+                }
+                Event::PeerExpired(peer_id) => {
+                    println!("mDNS discover peer has expired: {peer_id}");
+                }
+                Event::NewListenAddr(_) => {
+                    // Already printed by the runner itself; nothing more to do here.
                 }
             }
-            event = swarm.select_next_some() => match event {
-                SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
-                    for (peer_id, _multiaddr) in list {
-                        println!("mDNS discovered a new peer: {peer_id}");
-                        swarm.behaviour_mut().gossipsub.add_explicit_peer(&peer_id);
-                    }
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
-                    for (peer_id, _multiaddr) in list {
-                        println!("mDNS discover peer has expired: {peer_id}");
-                        swarm.behaviour_mut().gossipsub.remove_explicit_peer(&peer_id);
-                    }
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
-                    propagation_source: peer_id,
-                    message_id: id,
-                    message,
-                })) => {
-                    println!(
-                        "Got message: '{}' with id: {id} from peer: {peer_id}",
-                        String::from_utf8_lossy(&message.data),
-                    );
-
-                    // Deserialize the transaction
-                    let transaction: TransactionMessage = bincode::deserialize(&message.data).unwrap();
-                    println!(
-                        "Received transaction: {:?} with id: {id} from peer: {peer_id}",
-                        transaction,
-                    );
-
-                    // Store the transaction in Kademlia
-                    let transaction_key = RecordKey::new(&format!("transaction: {}", id));
-                    let transaction_record = Record {
-                        key: transaction_key,
-                        value: message.data.clone(),
-                        publisher: Some(peer_id),
-                        expires: None,
-                    };
-                    swarm.behaviour_mut().kademlia.put_record(transaction_record, Quorum::One)?;
+        }
+        drop(node_for_events);
+    });
 
-                    // Store the peer ID in Kademlia
-                    let peer_key = format!("peer:{}", peer_id);
-                    let peer_key = RecordKey::new(&format!("peer:{}", peer_id));
-                    let peer_record = Record {
-                        key: peer_key,
-                        value: peer_id.to_bytes(),
-                        publisher: Some(peer_id),
-                        expires: None,
-                    };
-                    swarm.behaviour_mut().kademlia.put_record(peer_record, Quorum::One)?;
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Kademlia(kad::Event::RoutingUpdated {
-                    peer,
-                    ..
-                })) => {
-                    println!("Routing table updated, peer joined DHT: {peer}");
-                },
-                SwarmEvent::Behaviour(MyBehaviourEvent::Kademlia(kad::Event::OutboundQueryProgressed{
-                    result,
-                    ..
-                })) => {
-                    println!("Outbound query progressed!");
-                },
-                SwarmEvent::NewListenAddr { address, .. } => {
-                    println!("Local node is listening on {address}");
-                },
-                _ => {}
-            }
+    // Read full lines from stdin and sign+publish a transaction per line.
+    let mut stdin = io::BufReader::new(io::stdin()).lines();
+    while let Ok(Some(line)) = stdin.next_line().await {
+        let transaction = node.create_transaction(line.into_bytes());
+        match node.publish_transaction(transaction.clone()).await {
+            Ok(_) => println!("Published transaction: {transaction:?}"),
+            Err(e) => println!("Publish error: {e:?}"),
         }
     }
+
+    Ok(())
 }
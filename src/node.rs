@@ -0,0 +1,910 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    error::Error,
+    hash::{Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use libp2p::{
+    futures::StreamExt,
+    gossipsub, kad,
+    kad::{store::MemoryStore as TStore, QueryId, Record, RecordKey},
+    mdns,
+    multiaddr::Protocol,
+    swarm::SwarmEvent,
+    PeerId, Transport,
+};
+use tokio::io;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::behaviour::{MyBehaviour, MyBehaviourEvent};
+use crate::broadcast::{
+    Batch, BatchBuffer, Kind, ReliableBroadcast, TransactionId, ECHO_TOPIC, READY_TOPIC,
+};
+use crate::config::NodeConfig;
+use crate::transaction::TransactionMessage;
+
+/// Well-known key nodes `start_providing` so that a fresh node can
+/// discover who holds transaction history via `get_providers`.
+const TRANSACTIONS_PROVIDER_KEY: &str = "transactions";
+
+/// Commands a [`Node`] handle can send to its [`NodeRunner`].
+pub enum Command {
+    PublishTransaction {
+        transaction: TransactionMessage,
+        sender: oneshot::Sender<Result<gossipsub::MessageId, gossipsub::PublishError>>,
+    },
+    GetRecord {
+        key: RecordKey,
+        sender: oneshot::Sender<Option<Record>>,
+    },
+    /// Looks up a previously-seen transaction by the gossipsub message id it
+    /// was stored under.
+    GetTransaction {
+        id: String,
+        sender: oneshot::Sender<Option<TransactionMessage>>,
+    },
+    /// Advertises this node as a provider of transaction history.
+    StartProviding { sender: oneshot::Sender<()> },
+    /// Finds peers providing transaction history. Resolves to an empty set
+    /// if none are known yet, rather than hanging.
+    GetProviders {
+        sender: oneshot::Sender<HashSet<PeerId>>,
+    },
+    GetPeers {
+        sender: oneshot::Sender<Vec<PeerId>>,
+    },
+    Bootstrap {
+        sender: oneshot::Sender<Result<QueryId, kad::NoKnownPeers>>,
+    },
+}
+
+/// Events emitted by the [`NodeRunner`] as the swarm makes progress.
+#[derive(Debug, Clone)]
+pub enum Event {
+    TransactionReceived {
+        transaction: TransactionMessage,
+        source: PeerId,
+    },
+    PeerDiscovered(PeerId),
+    PeerExpired(PeerId),
+    /// A transport started listening on `address`, e.g. so a bootstrap node
+    /// can hand its address to other nodes (tests do this over loopback TCP,
+    /// in lieu of mDNS/a public bootstrap list).
+    NewListenAddr(libp2p::Multiaddr),
+}
+
+/// A cheaply cloneable handle used to drive a [`NodeRunner`] from anywhere,
+/// e.g. a CLI loop or an embedding application.
+#[derive(Clone)]
+pub struct Node {
+    command_sender: mpsc::Sender<Command>,
+    local_peer_id: PeerId,
+    keypair: libp2p::identity::Keypair,
+}
+
+impl Node {
+    pub fn local_peer_id(&self) -> PeerId {
+        self.local_peer_id
+    }
+
+    /// Builds and signs a transaction over `tx_data` with this node's identity key.
+    pub fn create_transaction(&self, tx_data: Vec<u8>) -> TransactionMessage {
+        TransactionMessage::signed(&self.keypair, tx_data)
+    }
+
+    pub async fn publish_transaction(
+        &self,
+        transaction: TransactionMessage,
+    ) -> Result<gossipsub::MessageId, gossipsub::PublishError> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::PublishTransaction {
+                transaction,
+                sender,
+            })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+
+    pub async fn get_record(&self, key: RecordKey) -> Option<Record> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::GetRecord { key, sender })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+
+    pub async fn get_transaction(&self, id: String) -> Option<TransactionMessage> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::GetTransaction { id, sender })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+
+    pub async fn start_providing(&self) {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::StartProviding { sender })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+
+    pub async fn get_providers(&self) -> HashSet<PeerId> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::GetProviders { sender })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+
+    pub async fn get_peers(&self) -> Vec<PeerId> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::GetPeers { sender })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+
+    pub async fn bootstrap(&self) -> Result<QueryId, kad::NoKnownPeers> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(Command::Bootstrap { sender })
+            .await
+            .expect("Command receiver not to be dropped.");
+        receiver.await.expect("Sender not to be dropped.")
+    }
+}
+
+/// Tracks what a pending outbound Kademlia query should do once it resolves.
+enum PendingQuery {
+    GetRecord(oneshot::Sender<Option<Record>>),
+    GetTransaction(oneshot::Sender<Option<TransactionMessage>>),
+    StartProviding(oneshot::Sender<()>),
+    GetProviders {
+        sender: oneshot::Sender<HashSet<PeerId>>,
+        providers: HashSet<PeerId>,
+    },
+}
+
+/// Owns the [`libp2p::Swarm`] and drives it, resolving [`Command`]s against
+/// it and forwarding swarm activity as [`Event`]s.
+pub struct NodeRunner {
+    swarm: libp2p::Swarm<MyBehaviour>,
+    command_receiver: mpsc::Receiver<Command>,
+    event_sender: mpsc::Sender<Event>,
+    topic: gossipsub::IdentTopic,
+    pending_queries: HashMap<QueryId, PendingQuery>,
+    /// Peers named by `--bootstrap`/`CHAIN_GOSSIP_BOOTSTRAP_PEERS` we're
+    /// waiting to connect to before kicking off `kademlia.bootstrap()`.
+    bootstrap_peers: HashSet<PeerId>,
+    bootstrapped: bool,
+    echo_topic: gossipsub::IdentTopic,
+    ready_topic: gossipsub::IdentTopic,
+    broadcast: ReliableBroadcast,
+    echo_batch: BatchBuffer,
+    ready_batch: BatchBuffer,
+    batch_flush_interval: tokio::time::Interval,
+    /// Transactions we've verified but not yet delivered, keyed by the id
+    /// their `Echo`/`Ready` attestations reference, alongside when they were
+    /// added so [`Self::prune_stale`] can age them out.
+    pending_transactions: HashMap<TransactionId, (TransactionMessage, PeerId, Instant)>,
+    /// Ids that crossed the delivery threshold before their payload arrived;
+    /// delivered as soon as the payload does. Value is when the id was added,
+    /// for [`Self::prune_stale`].
+    ready_for_delivery: HashMap<TransactionId, Instant>,
+    /// TTL applied to records this node puts, mirrored onto `Record::expires`
+    /// explicitly rather than relying only on the `kad::Config` default.
+    record_ttl: Duration,
+    /// How long an entry may sit in `pending_transactions`/`ready_for_delivery`
+    /// without being delivered before [`Self::prune_stale`] evicts it.
+    broadcast_state_ttl: Duration,
+}
+
+/// Builds a [`Node`]/[`NodeRunner`] pair along with the event stream the
+/// runner will publish to. The runner must be `.run()` on a task of its own.
+pub async fn new_node(
+    config: NodeConfig,
+) -> Result<(Node, NodeRunner, mpsc::Receiver<Event>), Box<dyn Error>> {
+    // Generate our identity up front so the `Node` handle can keep a copy to
+    // sign transactions with, separate from the swarm that owns it.
+    let keypair = libp2p::identity::Keypair::generate_ed25519();
+
+    // Wire up TCP, QUIC and WebSocket side by side (via the builder's
+    // fallback/`Either` transport) so this node can talk to peers that are
+    // only reachable over one of them, e.g. a browser peer behind WebSocket.
+    let mut swarm = libp2p::SwarmBuilder::with_existing_identity(keypair.clone())
+        .with_tokio()
+        .with_tcp(
+            libp2p::tcp::Config::default(),
+            libp2p::noise::Config::new,
+            libp2p::yamux::Config::default,
+        )?
+        .with_quic()
+        // `with_tcp().with_quic()` already lands us in `OtherTransportPhase`,
+        // which has no fluent `with_websocket` of its own (that only exists
+        // right after `TcpPhase`/`QuicPhase`), so splice a hand-built
+        // WebSocket transport in via `with_other_transport` instead and let
+        // `with_dns` below wrap the combined transport in DNS resolution.
+        .with_other_transport(|key| {
+            let websocket = libp2p::websocket::WsConfig::new(
+                libp2p::dns::tokio::Transport::system(libp2p::tcp::tokio::Transport::new(
+                    libp2p::tcp::Config::default(),
+                ))?,
+            )
+            .upgrade(libp2p::core::upgrade::Version::V1Lazy)
+            .authenticate(libp2p::noise::Config::new(key)?)
+            .multiplex(libp2p::yamux::Config::default())
+            .map(|(peer_id, muxer), _| (peer_id, libp2p::core::muxing::StreamMuxerBox::new(muxer)));
+            Ok::<_, Box<dyn std::error::Error + Send + Sync>>(websocket)
+        })?
+        .with_dns()?
+        .with_behaviour(|key| {
+            // To content-address message, we can take the hash of message and use it as an ID.
+            let message_id_fn = |message: &libp2p::gossipsub::Message| {
+                let mut s = DefaultHasher::new();
+                message.data.hash(&mut s);
+                libp2p::gossipsub::MessageId::from(s.finish().to_string())
+            };
+
+            // Validation is `Permissive` + `validate_messages()`: gossipsub delivers
+            // every message to us, and we report accept/reject ourselves once we've
+            // checked the transaction's signature, instead of it enforcing that.
+            let gossipsub_config = gossipsub::ConfigBuilder::default()
+                .heartbeat_interval(Duration::from_secs(5))
+                .validation_mode(gossipsub::ValidationMode::Permissive)
+                .validate_messages()
+                .message_id_fn(message_id_fn)
+                .build()
+                .map_err(io::Error::other)?;
+
+            // Build a gossipsub network behaviour
+            let gossipsub = gossipsub::Behaviour::new(
+                gossipsub::MessageAuthenticity::Signed(key.clone()),
+                gossipsub_config,
+            )?;
+
+            // Build a kademlia network behaviour, bounding the store so a flood
+            // of gossiped transactions can't memory-exhaust it, and wiring up
+            // TTL/republication so still-valid records outlive their publisher.
+            let store_config = kad::store::MemoryStoreConfig {
+                max_records: config.max_records,
+                max_value_bytes: config.max_record_size_bytes,
+                ..Default::default()
+            };
+            let store = TStore::with_config(key.public().to_peer_id(), store_config);
+
+            let mut kademlia_config = kad::Config::new(kad::PROTOCOL_NAME);
+            kademlia_config.set_record_ttl(Some(config.record_ttl));
+            kademlia_config.set_provider_record_ttl(Some(config.record_ttl));
+            kademlia_config.set_publication_interval(Some(config.record_republish_interval));
+            kademlia_config
+                .set_provider_publication_interval(Some(config.record_republish_interval));
+
+            let kademlia =
+                kad::Behaviour::with_config(key.public().to_peer_id(), store, kademlia_config);
+
+            // Build a mdns network behaviour
+            let mdns =
+                mdns::tokio::Behaviour::new(mdns::Config::default(), key.public().to_peer_id())?;
+
+            Ok(MyBehaviour {
+                gossipsub,
+                mdns,
+                kademlia,
+            })
+        })?
+        .build();
+
+    // Subscribe to the transaction topic plus the double-echo reliable
+    // broadcast's echo/ready attestation topics.
+    let topic = gossipsub::IdentTopic::new("transaction");
+    swarm.behaviour_mut().gossipsub.subscribe(&topic)?;
+    let echo_topic = gossipsub::IdentTopic::new(ECHO_TOPIC);
+    swarm.behaviour_mut().gossipsub.subscribe(&echo_topic)?;
+    let ready_topic = gossipsub::IdentTopic::new(READY_TOPIC);
+    swarm.behaviour_mut().gossipsub.subscribe(&ready_topic)?;
+
+    // Listen on whichever transports the config enables.
+    if config.enable_quic {
+        swarm.listen_on("/ip4/0.0.0.0/udp/0/quic-v1".parse().unwrap())?;
+    }
+    if config.enable_tcp {
+        swarm.listen_on("/ip4/0.0.0.0/tcp/0".parse().unwrap())?;
+    }
+    if config.enable_websocket {
+        swarm.listen_on("/ip4/0.0.0.0/tcp/0/ws".parse().unwrap())?;
+    }
+
+    // Dial the configured bootstrap nodes and seed Kademlia's routing table
+    // with them so `bootstrap()` has somewhere to start from once connected.
+    let mut bootstrap_peers = HashSet::new();
+    for addr in config.bootstrap_peers {
+        let Some(peer_id) = addr.iter().find_map(|protocol| match protocol {
+            Protocol::P2p(peer_id) => Some(peer_id),
+            _ => None,
+        }) else {
+            println!("Ignoring bootstrap address without a /p2p/<peer-id> suffix: {addr}");
+            continue;
+        };
+        swarm
+            .behaviour_mut()
+            .kademlia
+            .add_address(&peer_id, addr.clone());
+        swarm.dial(addr)?;
+        bootstrap_peers.insert(peer_id);
+    }
+
+    let local_peer_id = *swarm.local_peer_id();
+    let (command_sender, command_receiver) = mpsc::channel(32);
+    let (event_sender, event_receiver) = mpsc::channel(32);
+
+    let node = Node {
+        command_sender,
+        local_peer_id,
+        keypair,
+    };
+    let runner = NodeRunner {
+        swarm,
+        command_receiver,
+        event_sender,
+        topic,
+        pending_queries: HashMap::new(),
+        bootstrap_peers,
+        bootstrapped: false,
+        echo_topic,
+        ready_topic,
+        broadcast: ReliableBroadcast::new(
+            config.echo_threshold,
+            config.ready_amplification_threshold,
+            config.delivery_threshold,
+            config.broadcast_state_ttl,
+            config.max_tracked_transactions,
+        ),
+        echo_batch: BatchBuffer::new(config.batch_max_entries),
+        ready_batch: BatchBuffer::new(config.batch_max_entries),
+        batch_flush_interval: tokio::time::interval(config.batch_flush_interval),
+        pending_transactions: HashMap::new(),
+        ready_for_delivery: HashMap::new(),
+        record_ttl: config.record_ttl,
+        broadcast_state_ttl: config.broadcast_state_ttl,
+    };
+
+    Ok((node, runner, event_receiver))
+}
+
+impl NodeRunner {
+    pub async fn run(mut self) {
+        loop {
+            tokio::select! {
+                command = self.command_receiver.recv() => match command {
+                    Some(c) => self.handle_command(c).await,
+                    // All `Node` handles were dropped, nothing left to drive.
+                    None => return,
+                },
+                event = self.swarm.select_next_some() => self.handle_swarm_event(event).await,
+                _ = self.batch_flush_interval.tick() => {
+                    self.flush_batches();
+                    self.prune_stale();
+                }
+            }
+        }
+    }
+
+    /// Flushes whatever echo/ready attestations have accumulated since the
+    /// last flush, even if neither batch is full yet.
+    fn flush_batches(&mut self) {
+        if !self.echo_batch.is_empty() {
+            let batch = self.echo_batch.take();
+            self.publish_batch(self.echo_topic.clone(), batch);
+        }
+        if !self.ready_batch.is_empty() {
+            let batch = self.ready_batch.take();
+            self.publish_batch(self.ready_topic.clone(), batch);
+        }
+    }
+
+    /// Bounds the echo/ready tracking state that would otherwise grow without
+    /// limit from transactions whose protocol run stalls (a payload that
+    /// never arrives, or a peer that never crosses the delivery threshold).
+    /// Piggybacks on the batch flush timer rather than running its own.
+    fn prune_stale(&mut self) {
+        self.broadcast.prune();
+        let now = Instant::now();
+        let ttl = self.broadcast_state_ttl;
+        self.pending_transactions
+            .retain(|_, (_, _, added_at)| now.duration_since(*added_at) < ttl);
+        self.ready_for_delivery
+            .retain(|_, added_at| now.duration_since(*added_at) < ttl);
+    }
+
+    fn publish_batch(&mut self, topic: gossipsub::IdentTopic, batch: Batch) {
+        let serialized = bincode::serialize(&batch).expect("Batch is always serializable");
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .publish(topic, serialized);
+    }
+
+    fn queue_echo(&mut self, id: TransactionId) {
+        if let Some(batch) = self.echo_batch.push(id, Kind::Echo) {
+            self.publish_batch(self.echo_topic.clone(), batch);
+        }
+    }
+
+    fn queue_ready(&mut self, id: TransactionId) {
+        if let Some(batch) = self.ready_batch.push(id, Kind::Ready) {
+            self.publish_batch(self.ready_topic.clone(), batch);
+        }
+    }
+
+    /// Delivers a transaction: stores it (and its sender) in Kademlia and
+    /// emits [`Event::TransactionReceived`]. Never called twice for the same
+    /// id, since [`ReliableBroadcast`] only reports `should_deliver` once.
+    async fn deliver_transaction(
+        &mut self,
+        id: TransactionId,
+        transaction: TransactionMessage,
+        source: PeerId,
+    ) {
+        // The id's `delivered: true` state is kept (until `prune` ages it
+        // out) so later readys for it can't report `should_deliver` again.
+        let serialized =
+            bincode::serialize(&transaction).expect("TransactionMessage is always serializable");
+
+        // Store the transaction in Kademlia so it can be looked up by id later,
+        // under the same key `Command::GetTransaction` builds from the
+        // original gossipsub message id.
+        let transaction_key =
+            RecordKey::new(&format!("transaction: {}", String::from_utf8_lossy(&id)));
+        let transaction_record = Record {
+            key: transaction_key,
+            value: serialized,
+            publisher: Some(source),
+            expires: Some(Instant::now() + self.record_ttl),
+        };
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(transaction_record, kad::Quorum::One);
+
+        // Store the peer ID in Kademlia
+        let peer_key = RecordKey::new(&format!("peer:{source}"));
+        let peer_record = Record {
+            key: peer_key,
+            value: source.to_bytes(),
+            publisher: Some(source),
+            expires: Some(Instant::now() + self.record_ttl),
+        };
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .kademlia
+            .put_record(peer_record, kad::Quorum::One);
+
+        let _ = self
+            .event_sender
+            .send(Event::TransactionReceived {
+                transaction,
+                source,
+            })
+            .await;
+    }
+
+    async fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::PublishTransaction {
+                transaction,
+                sender,
+            } => {
+                let serialized = bincode::serialize(&transaction).unwrap();
+                let result = self
+                    .swarm
+                    .behaviour_mut()
+                    .gossipsub
+                    .publish(self.topic.clone(), serialized);
+                let _ = sender.send(result);
+            }
+            Command::GetRecord { key, sender } => {
+                let query_id = self.swarm.behaviour_mut().kademlia.get_record(key);
+                self.pending_queries
+                    .insert(query_id, PendingQuery::GetRecord(sender));
+            }
+            Command::GetTransaction { id, sender } => {
+                let key = RecordKey::new(&format!("transaction: {id}"));
+                let query_id = self.swarm.behaviour_mut().kademlia.get_record(key);
+                self.pending_queries
+                    .insert(query_id, PendingQuery::GetTransaction(sender));
+            }
+            Command::StartProviding { sender } => {
+                let key = RecordKey::new(&TRANSACTIONS_PROVIDER_KEY);
+                let query_id = self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .start_providing(key)
+                    .expect("store to accept the provider record");
+                self.pending_queries
+                    .insert(query_id, PendingQuery::StartProviding(sender));
+            }
+            Command::GetProviders { sender } => {
+                let key = RecordKey::new(&TRANSACTIONS_PROVIDER_KEY);
+                let query_id = self.swarm.behaviour_mut().kademlia.get_providers(key);
+                self.pending_queries.insert(
+                    query_id,
+                    PendingQuery::GetProviders {
+                        sender,
+                        providers: HashSet::new(),
+                    },
+                );
+            }
+            Command::GetPeers { sender } => {
+                let peers = self
+                    .swarm
+                    .behaviour_mut()
+                    .kademlia
+                    .kbuckets()
+                    .flat_map(|bucket| {
+                        bucket
+                            .iter()
+                            .map(|entry| *entry.node.key.preimage())
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                let _ = sender.send(peers);
+            }
+            Command::Bootstrap { sender } => {
+                let result = self.swarm.behaviour_mut().kademlia.bootstrap();
+                let _ = sender.send(result);
+            }
+        }
+    }
+
+    async fn handle_swarm_event(&mut self, event: SwarmEvent<MyBehaviourEvent>) {
+        match event {
+            SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Discovered(list))) => {
+                for (peer_id, _multiaddr) in list {
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .add_explicit_peer(&peer_id);
+                    let _ = self.event_sender.send(Event::PeerDiscovered(peer_id)).await;
+                }
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Mdns(mdns::Event::Expired(list))) => {
+                for (peer_id, _multiaddr) in list {
+                    self.swarm
+                        .behaviour_mut()
+                        .gossipsub
+                        .remove_explicit_peer(&peer_id);
+                    let _ = self.event_sender.send(Event::PeerExpired(peer_id)).await;
+                }
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Gossipsub(gossipsub::Event::Message {
+                propagation_source: peer_id,
+                message_id: id,
+                message,
+            })) => self.handle_gossipsub_message(peer_id, id, message).await,
+            SwarmEvent::Behaviour(MyBehaviourEvent::Kademlia(kad::Event::RoutingUpdated {
+                peer,
+                ..
+            })) => {
+                println!("Routing table updated, peer joined DHT: {peer}");
+            }
+            SwarmEvent::Behaviour(MyBehaviourEvent::Kademlia(
+                kad::Event::OutboundQueryProgressed { id, result, .. },
+            )) => self.handle_query_progressed(id, result),
+            SwarmEvent::NewListenAddr { address, .. } => {
+                println!("Local node is listening on {address}");
+                let _ = self
+                    .event_sender
+                    .send(Event::NewListenAddr(address))
+                    .await;
+            }
+            SwarmEvent::ConnectionEstablished { peer_id, .. }
+                if !self.bootstrapped && self.bootstrap_peers.contains(&peer_id) =>
+            {
+                self.bootstrapped = true;
+                if let Err(e) = self.swarm.behaviour_mut().kademlia.bootstrap() {
+                    println!("Failed to start DHT bootstrap: {e}");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn handle_gossipsub_message(
+        &mut self,
+        peer_id: PeerId,
+        id: gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) {
+        if message.topic == self.topic.hash() {
+            self.handle_transaction_message(peer_id, id, message).await;
+            return;
+        }
+
+        // Control topics carry no application-level claim to verify, so
+        // there's nothing to reject; accept and let the batch itself fail to
+        // deserialize quietly if it's malformed.
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(&id, &peer_id, gossipsub::MessageAcceptance::Accept);
+
+        let Ok(batch) = bincode::deserialize::<Batch>(&message.data) else {
+            return;
+        };
+
+        // Vote-count by the attestation's authenticated originator, not
+        // `peer_id` (the immediate neighbor that relayed it to us): under
+        // multi-hop gossip many distinct attesters can share one relay, and
+        // crediting the relay instead would undercount echoes/readys and
+        // stall delivery below `echo_threshold`/`delivery_threshold` even
+        // once enough distinct peers have genuinely attested. We publish
+        // with `MessageAuthenticity::Signed`, so our own batches always
+        // carry `source`; `ValidationMode::Permissive` still lets a peer
+        // publish without it, so fall back to dropping the batch rather
+        // than crediting a vote to nobody.
+        let Some(from) = message.source else {
+            return;
+        };
+        if message.topic == self.echo_topic.hash() {
+            self.handle_batch(from, batch, Kind::Echo).await;
+        } else if message.topic == self.ready_topic.hash() {
+            self.handle_batch(from, batch, Kind::Ready).await;
+        }
+    }
+
+    async fn handle_transaction_message(
+        &mut self,
+        peer_id: PeerId,
+        id: gossipsub::MessageId,
+        message: gossipsub::Message,
+    ) {
+        // With `ValidationMode::Permissive` gossipsub won't check anything for
+        // us, so an unparseable or badly-signed transaction must be rejected
+        // here or it would keep being re-propagated to the rest of the mesh.
+        let transaction = bincode::deserialize::<TransactionMessage>(&message.data)
+            .ok()
+            .filter(TransactionMessage::verify);
+
+        let Some(transaction) = transaction else {
+            let _ = self
+                .swarm
+                .behaviour_mut()
+                .gossipsub
+                .report_message_validation_result(
+                    &id,
+                    &peer_id,
+                    gossipsub::MessageAcceptance::Reject,
+                );
+            return;
+        };
+        let _ = self
+            .swarm
+            .behaviour_mut()
+            .gossipsub
+            .report_message_validation_result(&id, &peer_id, gossipsub::MessageAcceptance::Accept);
+
+        let tx_id: TransactionId = id.to_string().into_bytes();
+
+        // The ready threshold may have already been crossed by echoes/readys
+        // that arrived before this payload did; deliver immediately if so.
+        if self.ready_for_delivery.remove(&tx_id).is_some() {
+            self.deliver_transaction(tx_id, transaction, peer_id).await;
+            return;
+        }
+
+        self.pending_transactions
+            .insert(tx_id.clone(), (transaction, peer_id, Instant::now()));
+        if self.broadcast.on_transaction_seen(tx_id.clone()) {
+            self.queue_echo(tx_id);
+        }
+    }
+
+    /// Applies a batch of `Echo`/`Ready` attestations from `from`, queueing
+    /// our own echo/ready broadcasts and delivering transactions as the
+    /// double-echo thresholds are crossed.
+    async fn handle_batch(&mut self, from: PeerId, batch: Batch, expected_kind: Kind) {
+        for (id, kind) in batch.entries {
+            if kind != expected_kind {
+                // A batch should only ever carry the kind for the topic it
+                // was published on; ignore anything that doesn't match.
+                continue;
+            }
+            match kind {
+                Kind::Echo => {
+                    if self.broadcast.on_echo(id.clone(), from) {
+                        self.queue_ready(id);
+                    }
+                }
+                Kind::Ready => {
+                    let (should_amplify, should_deliver) =
+                        self.broadcast.on_ready(id.clone(), from);
+                    if should_amplify {
+                        self.queue_ready(id.clone());
+                    }
+                    if should_deliver {
+                        match self.pending_transactions.remove(&id) {
+                            Some((transaction, source, _)) => {
+                                self.deliver_transaction(id, transaction, source).await
+                            }
+                            // Payload hasn't arrived yet; deliver as soon as it does.
+                            None => {
+                                self.ready_for_delivery.insert(id, Instant::now());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_query_progressed(&mut self, id: QueryId, result: kad::QueryResult) {
+        match result {
+            kad::QueryResult::GetRecord(Ok(kad::GetRecordOk::FoundRecord(kad::PeerRecord {
+                record,
+                ..
+            }))) => match self.pending_queries.remove(&id) {
+                Some(PendingQuery::GetRecord(sender)) => {
+                    let _ = sender.send(Some(record));
+                }
+                Some(PendingQuery::GetTransaction(sender)) => {
+                    let transaction = bincode::deserialize(&record.value).ok();
+                    let _ = sender.send(transaction);
+                }
+                _ => {}
+            },
+            // Explicitly surface the "nothing found" outcomes instead of letting the
+            // caller's oneshot silently drop.
+            kad::QueryResult::GetRecord(Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord {
+                ..
+            }))
+            | kad::QueryResult::GetRecord(Err(_)) => match self.pending_queries.remove(&id) {
+                Some(PendingQuery::GetRecord(sender)) => {
+                    let _ = sender.send(None);
+                }
+                Some(PendingQuery::GetTransaction(sender)) => {
+                    let _ = sender.send(None);
+                }
+                _ => {}
+            },
+            kad::QueryResult::StartProviding(_) => {
+                if let Some(PendingQuery::StartProviding(sender)) = self.pending_queries.remove(&id)
+                {
+                    let _ = sender.send(());
+                }
+            }
+            kad::QueryResult::GetProviders(Ok(kad::GetProvidersOk::FoundProviders {
+                providers,
+                ..
+            })) => {
+                if let Some(PendingQuery::GetProviders {
+                    providers: known, ..
+                }) = self.pending_queries.get_mut(&id)
+                {
+                    known.extend(providers);
+                }
+            }
+            // A node's providers only show up here once it has actually been inserted
+            // into our routing table; until then this fires with an empty set, which
+            // we must still report rather than leaving the caller waiting forever.
+            kad::QueryResult::GetProviders(Ok(
+                kad::GetProvidersOk::FinishedWithNoAdditionalRecord { .. },
+            ))
+            | kad::QueryResult::GetProviders(Err(_)) => {
+                if let Some(PendingQuery::GetProviders { sender, providers }) =
+                    self.pending_queries.remove(&id)
+                {
+                    let _ = sender.send(providers);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Spins up two `NodeRunner`s over loopback TCP (node B first, dialed by
+    /// node A via `bootstrap_peers` once we learn B's listen address from its
+    /// event stream) and asserts a transaction A publishes is eventually
+    /// delivered to B as `Event::TransactionReceived`. This is the gossipsub
+    /// double-echo path end to end, not a single function in isolation.
+    #[tokio::test]
+    async fn published_transaction_is_delivered_to_peer() {
+        // The default thresholds assume a swarm of 3+ peers (`delivery_threshold: 3`
+        // distinct readys); with only two nodes in this test, at most one distinct
+        // peer can ever echo/ready, so they're lowered to what two nodes can reach.
+        let config_b = NodeConfig {
+            enable_quic: false,
+            enable_websocket: false,
+            echo_threshold: 1,
+            ready_amplification_threshold: 1,
+            delivery_threshold: 1,
+            ..Default::default()
+        };
+        let (node_b, runner_b, mut events_b) =
+            new_node(config_b).await.expect("node B starts");
+        tokio::spawn(runner_b.run());
+
+        let b_listen_addr = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match events_b.recv().await.expect("node B's event channel stays open") {
+                    Event::NewListenAddr(addr)
+                        if addr.iter().any(|p| matches!(p, Protocol::Tcp(_))) =>
+                    {
+                        return addr;
+                    }
+                    _ => {}
+                }
+            }
+        })
+        .await
+        .expect("node B listens on TCP within the timeout");
+
+        let config_a = NodeConfig {
+            enable_quic: false,
+            enable_websocket: false,
+            bootstrap_peers: vec![b_listen_addr.with(Protocol::P2p(node_b.local_peer_id()))],
+            echo_threshold: 1,
+            ready_amplification_threshold: 1,
+            delivery_threshold: 1,
+            ..Default::default()
+        };
+        let (node_a, runner_a, mut events_a) =
+            new_node(config_a).await.expect("node A starts");
+        tokio::spawn(runner_a.run());
+        // The runner's event channel is bounded and sent to with a blocking
+        // `.await`, so it must be drained continuously (as `main.rs` does)
+        // or a full buffer would stall the runner's whole select loop,
+        // command processing included.
+        tokio::spawn(async move { while events_a.recv().await.is_some() {} });
+
+        let tx_data = b"integration-test-transaction".to_vec();
+        let transaction = node_a.create_transaction(tx_data.clone());
+
+        // `events_b` must keep being drained for the rest of the test (its
+        // channel is bounded and `NodeRunner` sends to it with a blocking
+        // `.await`), so retry publishing on a timer interleaved with reading
+        // it, rather than blocking on publish retries with nothing reading
+        // `events_b` in between.
+        let mut publish_retry = tokio::time::interval(Duration::from_millis(200));
+        let delivered = tokio::time::timeout(Duration::from_secs(20), async {
+            loop {
+                tokio::select! {
+                    // The mesh isn't necessarily up the instant both swarms
+                    // connect, so keep retrying until gossipsub stops
+                    // reporting `InsufficientPeers`.
+                    _ = publish_retry.tick() => {
+                        let _ = node_a.publish_transaction(transaction.clone()).await;
+                    }
+                    event = events_b.recv() => {
+                        if let Event::TransactionReceived { transaction, .. } =
+                            event.expect("node B's event channel stays open")
+                        {
+                            return transaction;
+                        }
+                    }
+                }
+            }
+        })
+        .await
+        .expect("node B receives the transaction within the timeout");
+
+        assert_eq!(delivered.tx_data, tx_data);
+    }
+}
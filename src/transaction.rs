@@ -0,0 +1,91 @@
+use libp2p::identity::{Keypair, PublicKey};
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// A gossiped transaction, signed by its sender's node key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionMessage {
+    pub signature: Vec<u8>,
+    /// The sender's public key, protobuf-encoded, so a recipient can verify
+    /// `signature` without any prior key exchange.
+    pub sender: Vec<u8>,
+    pub timestamp: u64,
+    pub tx_data: Vec<u8>,
+}
+
+impl TransactionMessage {
+    /// Builds and signs a transaction over `tx_data` with `keypair`.
+    pub fn signed(keypair: &Keypair, tx_data: Vec<u8>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let sender = keypair.public().encode_protobuf();
+        let signature = keypair
+            .sign(&Self::canonical_bytes(&sender, timestamp, &tx_data))
+            .expect("node identities are ed25519 keys, which always support signing");
+
+        Self {
+            signature,
+            sender,
+            timestamp,
+            tx_data,
+        }
+    }
+
+    /// Verifies `signature` against the embedded `sender` public key.
+    pub fn verify(&self) -> bool {
+        match PublicKey::try_decode_protobuf(&self.sender) {
+            Ok(public_key) => public_key.verify(
+                &Self::canonical_bytes(&self.sender, self.timestamp, &self.tx_data),
+                &self.signature,
+            ),
+            Err(_) => false,
+        }
+    }
+
+    fn canonical_bytes(sender: &[u8], timestamp: u64, tx_data: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(sender.len() + 8 + tx_data.len());
+        bytes.extend_from_slice(sender);
+        bytes.extend_from_slice(&timestamp.to_be_bytes());
+        bytes.extend_from_slice(tx_data);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_transaction_verifies() {
+        let keypair = Keypair::generate_ed25519();
+        let transaction = TransactionMessage::signed(&keypair, b"hello".to_vec());
+        assert!(transaction.verify());
+    }
+
+    #[test]
+    fn tampered_tx_data_fails_verification() {
+        let keypair = Keypair::generate_ed25519();
+        let mut transaction = TransactionMessage::signed(&keypair, b"hello".to_vec());
+        transaction.tx_data = b"goodbye".to_vec();
+        assert!(!transaction.verify());
+    }
+
+    #[test]
+    fn tampered_signature_fails_verification() {
+        let keypair = Keypair::generate_ed25519();
+        let mut transaction = TransactionMessage::signed(&keypair, b"hello".to_vec());
+        transaction.signature[0] ^= 0xff;
+        assert!(!transaction.verify());
+    }
+
+    #[test]
+    fn signature_from_a_different_sender_fails_verification() {
+        let keypair = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+        let mut transaction = TransactionMessage::signed(&keypair, b"hello".to_vec());
+        transaction.sender = other.public().encode_protobuf();
+        assert!(!transaction.verify());
+    }
+}